@@ -9,6 +9,9 @@ mod erc721 {
         hashmap::Entry,
         HashMap as StorageHashMap
     };
+    use ink_prelude::collections::BTreeMap;
+    use ink_prelude::vec;
+    use ink_prelude::vec::Vec;
     use scale::{
         Decode,
         Encode,
@@ -16,6 +19,12 @@ mod erc721 {
 
     pub type TokenId = u32;
 
+    // Selector of the on_erc721_received callback.
+    pub const ON_ERC721_RECEIVED_SELECTOR: [u8; 4] = [0x15, 0x0b, 0x7a, 0x02];
+
+    // Domain tag mixed into bridge-mint receipts so they can't be replayed elsewhere.
+    const BRIDGE_MINT_DOMAIN: &[u8] = b"ink-erc721/bridge-mint";
+
     #[ink(storage)]
     #[derive(Default)]
     pub struct Erc721 {
@@ -23,6 +32,29 @@ mod erc721 {
         token_approvals: StorageHashMap<TokenId, AccountId>,
         owned_tokens_count: StorageHashMap<AccountId, u32>,
         operator_approves: StorageHashMap<(AccountId, AccountId), bool>,
+        // All minted tokens, for enumeration.
+        all_tokens: StorageHashMap<u32, TokenId>,
+        // Index of each token in all_tokens.
+        all_tokens_index: StorageHashMap<TokenId, u32>,
+        // Tokens owned by each account, for enumeration.
+        owned_tokens: StorageHashMap<(AccountId, u32), TokenId>,
+        // Index of each token in owned_tokens.
+        owned_tokens_index: StorageHashMap<TokenId, u32>,
+        total_supply: u32,
+        // Account allowed to sign bridge-mint receipts.
+        bridge_authority: AccountId,
+        // Nonces already used by mint_with_receipt.
+        consumed_nonces: StorageHashMap<u64, bool>,
+        // Nonce per owner for permit, bumped on use.
+        permit_nonces: StorageHashMap<AccountId, u64>,
+        // Quantity balances for semi-fungible ids, keyed by (id, owner).
+        balances: StorageHashMap<(TokenId, AccountId), u128>,
+        // Total minted for each semi-fungible id.
+        total_for_token: StorageHashMap<TokenId, u128>,
+        // Balance history per account for balance_of_at.
+        checkpoints: StorageHashMap<(AccountId, u32), (u32, u32)>,
+        // Number of checkpoints per account.
+        checkpoint_count: StorageHashMap<AccountId, u32>,
     }
 
     #[derive(Encode, Decode, Debug, PartialEq, Eq, Copy, Clone)]
@@ -37,6 +69,11 @@ mod erc721 {
         CannotRemove,
         CannotFetchValue,
         NotAllowed,
+        NotReceiver,
+        InvalidSignature,
+        ReceiptAlreadyUsed,
+        PermitExpired,
+        InsufficientBalance,
     }
 
     #[ink(event)]
@@ -68,14 +105,36 @@ mod erc721 {
         approved: bool,
     }
 
+    #[ink(event)]
+    pub struct TransferBatch {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        ids: Vec<TokenId>,
+        amounts: Vec<u128>,
+    }
+
     impl Erc721 {
         #[ink(constructor)]
-        pub fn new() -> Self {
+        pub fn new(bridge_authority: AccountId) -> Self {
             Self {
                 token_owner: Default::default(),
                 token_approvals: Default::default(),
                 owned_tokens_count: Default::default(),
                 operator_approves: Default::default(),
+                all_tokens: Default::default(),
+                all_tokens_index: Default::default(),
+                owned_tokens: Default::default(),
+                owned_tokens_index: Default::default(),
+                total_supply: 0,
+                bridge_authority,
+                consumed_nonces: Default::default(),
+                permit_nonces: Default::default(),
+                balances: Default::default(),
+                total_for_token: Default::default(),
+                checkpoints: Default::default(),
+                checkpoint_count: Default::default(),
             }
         }
 
@@ -84,6 +143,64 @@ mod erc721 {
             self.balance_of_or_zero(&owner)
         }
 
+        #[ink(message)]
+        pub fn total_supply(&self) -> u32 {
+            self.total_supply
+        }
+
+        #[ink(message)]
+        pub fn token_by_index(&self, index: u32) -> Option<TokenId> {
+            self.all_tokens.get(&index).cloned()
+        }
+
+        #[ink(message)]
+        pub fn token_of_owner_by_index(&self, owner: AccountId, index: u32) -> Option<TokenId> {
+            self.owned_tokens.get(&(owner, index)).cloned()
+        }
+
+        // Returns owner's balance as of block, from the checkpoint history.
+        #[ink(message)]
+        pub fn balance_of_at(&self, owner: AccountId, block: u32) -> u32 {
+            let count = *self.checkpoint_count.get(&owner).unwrap_or(&0);
+            if count == 0 {
+                return 0
+            };
+            let (first_block, _) = *self
+                .checkpoints
+                .get(&(owner, 0))
+                .expect("first checkpoint must exist");
+            if block < first_block {
+                return 0
+            };
+            let (last_block, last_balance) = *self
+                .checkpoints
+                .get(&(owner, count - 1))
+                .expect("last checkpoint must exist");
+            if block >= last_block {
+                return last_balance
+            };
+
+            // Binary search for the checkpoint at or before block.
+            let mut low = 0u32;
+            let mut high = count - 1;
+            while low < high {
+                let mid = low + (high - low + 1) / 2;
+                let (mid_block, _) = *self
+                    .checkpoints
+                    .get(&(owner, mid))
+                    .expect("checkpoint must exist");
+                if mid_block <= block {
+                    low = mid;
+                } else {
+                    high = mid - 1;
+                }
+            }
+            self.checkpoints
+                .get(&(owner, low))
+                .expect("checkpoint must exist")
+                .1
+        }
+
         #[ink(message)]
         pub fn owner_of(&self, id: TokenId) -> Option<AccountId> {
             self.token_owner.get(&id).cloned()
@@ -111,6 +228,43 @@ mod erc721 {
             Ok(())
         }
 
+        // Approves spender for id using owner's signature instead of owner calling directly.
+        #[ink(message)]
+        pub fn permit(
+            &mut self,
+            owner: AccountId,
+            spender: AccountId,
+            id: TokenId,
+            deadline: u64,
+            signature: [u8; 65],
+        ) -> Result<(), Error> {
+            if self.env().block_timestamp() > deadline {
+                return Err(Error::PermitExpired)
+            };
+
+            let nonce = *self.permit_nonces.get(&owner).unwrap_or(&0);
+            let mut hash = [0u8; 32];
+            ink_env::hash_bytes::<ink_env::hash::Keccak256>(
+                &(owner, spender, id, nonce, deadline).encode(),
+                &mut hash,
+            );
+
+            let compressed_pubkey = self
+                .env()
+                .ecdsa_recover(&signature, &hash)
+                .map_err(|_| Error::InvalidSignature)?;
+            if to_account_id(compressed_pubkey.as_ref()) != owner {
+                return Err(Error::InvalidSignature)
+            };
+            if self.owner_of(id) != Some(owner) {
+                return Err(Error::NotAllowed)
+            };
+
+            self.apply_approval(owner, &spender, id)?;
+            self.permit_nonces.insert(owner, nonce + 1);
+            Ok(())
+        }
+
         #[ink(message)]
         pub fn transfer(&mut self, destination: AccountId, id: TokenId) -> Result<(), Error> {
             let caller = self.env().caller();
@@ -124,6 +278,20 @@ mod erc721 {
             Ok(())
         }
 
+        // Like transfer_from but checks the receiver accepts the token if it's a contract.
+        #[ink(message)]
+        pub fn safe_transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            id: TokenId,
+            data: Vec<u8>,
+        ) -> Result<(), Error> {
+            self.transfer_token_from(&from, &to, id)?;
+            self.check_on_erc721_received(&from, &to, id, data)?;
+            Ok(())
+        }
+
         // Creates a new token.
         #[ink(message)]
         pub fn mint(&mut self, id: TokenId) -> Result<(), Error> {
@@ -137,25 +305,130 @@ mod erc721 {
             Ok(())
         }
 
+        // Mints a token bridged from another chain using a receipt signed by bridge_authority.
+        #[ink(message)]
+        pub fn mint_with_receipt(
+            &mut self,
+            id: TokenId,
+            recipient: AccountId,
+            nonce: u64,
+            signature: [u8; 65],
+        ) -> Result<(), Error> {
+            if *self.consumed_nonces.get(&nonce).unwrap_or(&false) {
+                return Err(Error::ReceiptAlreadyUsed)
+            }
+
+            let mut message = BRIDGE_MINT_DOMAIN.to_vec();
+            message.extend_from_slice(&(recipient, id, nonce).encode());
+            let mut hash = [0u8; 32];
+            ink_env::hash_bytes::<ink_env::hash::Keccak256>(&message, &mut hash);
+
+            let compressed_pubkey = self
+                .env()
+                .ecdsa_recover(&signature, &hash)
+                .map_err(|_| Error::InvalidSignature)?;
+            let signer = to_account_id(compressed_pubkey.as_ref());
+            if signer != self.bridge_authority {
+                return Err(Error::InvalidSignature)
+            }
+
+            self.consumed_nonces.insert(nonce, true);
+            self.add_token_to(&recipient, id)?;
+            self.env().emit_event(Transfer {
+                from: Some(AccountId::from([0x00; 32])),
+                to: Some(recipient),
+                id,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn balance_of_batch(&self, owners: Vec<AccountId>, ids: Vec<TokenId>) -> Result<Vec<u128>, Error> {
+            // Same mismatched-length check as batch_transfer, instead of silently
+            // zipping to the shorter vector.
+            if owners.len() != ids.len() {
+                return Err(Error::NotAllowed)
+            };
+            Ok(owners
+                .into_iter()
+                .zip(ids.into_iter())
+                .map(|(owner, id)| *self.balances.get(&(id, owner)).unwrap_or(&0))
+                .collect())
+        }
+
+        // Mints amount units of the semi-fungible id. An id already minted as a
+        // single-owner token cannot also hold a quantity balance.
+        #[ink(message)]
+        pub fn mint_amount(&mut self, id: TokenId, amount: u128) -> Result<(), Error> {
+            if self.token_owner.contains_key(&id) {
+                return Err(Error::TokenExists)
+            };
+            let caller = self.env().caller();
+            let balance_entry = self.balances.entry((id, caller));
+            increase_balance_of(balance_entry, amount);
+            let total_entry = self.total_for_token.entry(id);
+            increase_total_of(total_entry, amount);
+            Ok(())
+        }
+
+        // Moves several id balances to to in one call. An insufficient balance on
+        // any leg reverts the whole batch.
+        #[ink(message)]
+        pub fn batch_transfer(
+            &mut self,
+            to: AccountId,
+            ids: Vec<TokenId>,
+            amounts: Vec<u128>,
+        ) -> Result<(), Error> {
+            if ids.len() != amounts.len() {
+                return Err(Error::NotAllowed)
+            };
+            let caller = self.env().caller();
+
+            // Aggregate per id first, so a repeated id is checked against the total.
+            let mut aggregated: BTreeMap<TokenId, u128> = BTreeMap::new();
+            for (id, amount) in ids.iter().zip(amounts.iter()) {
+                *aggregated.entry(*id).or_insert(0) += *amount;
+            }
+
+            for (id, amount) in aggregated.iter() {
+                let balance = *self.balances.get(&(*id, caller)).unwrap_or(&0);
+                if balance < *amount {
+                    return Err(Error::InsufficientBalance)
+                };
+            }
+
+            for (id, amount) in aggregated.iter() {
+                let sender_balance = self
+                    .balances
+                    .get_mut(&(*id, caller))
+                    .expect("balance checked above");
+                *sender_balance -= *amount;
+                let receiver_entry = self.balances.entry((*id, to));
+                increase_balance_of(receiver_entry, *amount);
+            }
+
+            self.env().emit_event(TransferBatch {
+                from: caller,
+                to,
+                ids,
+                amounts,
+            });
+            Ok(())
+        }
+
         // Deletes an existiong token. Only the owner can burn the token.
         #[ink(message)]
         pub fn burn(&mut self, id: TokenId) -> Result<(), Error> {
             let caller = self.env().caller();
-            let Self {
-                token_owner,
-                owned_tokens_count,
-                ..
-            } = self;
-            let occupied = match token_owner.entry(id) {
-                Entry::Vacant(_) => return Err(Error::TokenNotFound),
-                Entry::Occupied(occupied) => occupied,
-            };
-            if occupied.get() != &caller {
-                return Err(Error::NotOwner)
+            match self.token_owner.get(&id) {
+                None => return Err(Error::TokenNotFound),
+                Some(owner) if owner != &caller => return Err(Error::NotOwner),
+                _ => {}
             };
 
-            decrease_counter_of(owned_tokens_count, &caller)?;
-            occupied.remove_entry();
+            // Same bookkeeping as a transfer away, so enumeration stays correct on burn.
+            self.remove_token_from(&caller, id)?;
             self.env().emit_event(Transfer {
                 from: Some(caller),
                 to: Some(AccountId::from([0x0; 32])),
@@ -183,6 +456,40 @@ mod erc721 {
             Ok(())
         }
 
+        // Calls on_erc721_received on to if it's a contract. Plain accounts are
+        // accepted without a callback.
+        fn check_on_erc721_received(
+            &self,
+            from: &AccountId,
+            to: &AccountId,
+            id: TokenId,
+            data: Vec<u8>,
+        ) -> Result<(), Error> {
+            if !self.env().is_contract(to) {
+                return Ok(())
+            };
+
+            let operator = self.env().caller();
+            let result = ink_env::call::build_call::<Environment>()
+                .call_type(ink_env::call::Call::new().callee(*to).gas_limit(0))
+                .exec_input(
+                    ink_env::call::ExecutionInput::new(ink_env::call::Selector::new(
+                        ON_ERC721_RECEIVED_SELECTOR,
+                    ))
+                    .push_arg(operator)
+                    .push_arg(*from)
+                    .push_arg(id)
+                    .push_arg(data),
+                )
+                .returns::<[u8; 4]>()
+                .fire();
+
+            match result {
+                Ok(selector) if selector == ON_ERC721_RECEIVED_SELECTOR => Ok(()),
+                _ => Err(Error::NotReceiver),
+            }
+        }
+
         fn approve_for(&mut self, to: &AccountId, id: TokenId) -> Result<(), Error> {
             let caller = self.env().caller();
             let owner = self.owner_of(id);
@@ -191,6 +498,12 @@ mod erc721 {
             {
                 return Err(Error::NotAllowed)
             };
+            self.apply_approval(caller, to, id)
+        }
+
+        // Records `to` as approved for `id` and emits `Approval`. Used by `approve_for`
+        // and by `permit`.
+        fn apply_approval(&mut self, owner: AccountId, to: &AccountId, id: TokenId) -> Result<(), Error> {
             if *to == AccountId::from([0x0; 32]) {
                 return Err(Error::NotAllowed)
             };
@@ -199,7 +512,7 @@ mod erc721 {
                 return Err(Error::CannotInsert)
             };
             self.env().emit_event(Approval {
-                from: caller,
+                from: owner,
                 to: *to,
                 id,
             });
@@ -207,9 +520,17 @@ mod erc721 {
         }
 
         fn add_token_to(&mut self, to: &AccountId, id: TokenId) -> Result<(), Error> {
+            if self.total_for_token.contains_key(&id) {
+                return Err(Error::TokenExists)
+            };
             let Self {
                 token_owner,
                 owned_tokens_count,
+                all_tokens,
+                all_tokens_index,
+                owned_tokens,
+                owned_tokens_index,
+                total_supply,
                 ..
             } = self;
             let vacant_token_owner = match token_owner.entry(id) {
@@ -222,6 +543,17 @@ mod erc721 {
             let entry = owned_tokens_count.entry(*to);
             increase_counter_of(entry);
             vacant_token_owner.insert(*to);
+
+            let supply_index = *total_supply;
+            all_tokens.insert(supply_index, id);
+            all_tokens_index.insert(id, supply_index);
+            *total_supply += 1;
+
+            let owner_index = *owned_tokens_count.get(to).unwrap_or(&0) - 1;
+            owned_tokens.insert((*to, owner_index), id);
+            owned_tokens_index.insert(id, owner_index);
+
+            self.record_checkpoint(to);
             Ok(())
         }
 
@@ -229,6 +561,15 @@ mod erc721 {
             *self.owned_tokens_count.get(of).unwrap_or(&0)
         }
 
+        // Appends owner's current balance to its checkpoint history.
+        fn record_checkpoint(&mut self, owner: &AccountId) {
+            let balance = self.balance_of_or_zero(owner);
+            let block_number = self.env().block_number();
+            let index = *self.checkpoint_count.get(owner).unwrap_or(&0);
+            self.checkpoints.insert((*owner, index), (block_number, balance));
+            self.checkpoint_count.insert(*owner, index + 1);
+        }
+
         fn approved_for_all(&self, owner: AccountId, operator: AccountId) -> bool {
             *self
                 .operator_approves
@@ -264,14 +605,45 @@ mod erc721 {
             let Self {
                 token_owner,
                 owned_tokens_count,
+                all_tokens,
+                all_tokens_index,
+                owned_tokens,
+                owned_tokens_index,
+                total_supply,
                 ..
             } = self;
             let occupied = match token_owner.entry(id) {
                 Entry::Vacant(_) => return Err(Error::TokenNotFound),
                 Entry::Occupied(occupied) => occupied,
             };
+            let owner_count_before = *owned_tokens_count.get(from).unwrap_or(&0);
             decrease_counter_of(owned_tokens_count, from)?;
             occupied.remove_entry();
+
+            // Swap-and-pop the removed token out of the dense global list.
+            *total_supply -= 1;
+            let last_index = *total_supply;
+            let removed_index = all_tokens_index.take(&id).unwrap_or(last_index);
+            if removed_index != last_index {
+                let last_token = *all_tokens.get(&last_index).expect("last token must exist");
+                all_tokens.insert(removed_index, last_token);
+                all_tokens_index.insert(last_token, removed_index);
+            }
+            all_tokens.take(&last_index);
+
+            // Swap-and-pop the removed token out of the owner's dense list.
+            let last_owner_index = owner_count_before - 1;
+            let removed_owner_index = owned_tokens_index.take(&id).unwrap_or(last_owner_index);
+            if removed_owner_index != last_owner_index {
+                let last_owner_token = *owned_tokens
+                    .get(&(*from, last_owner_index))
+                    .expect("last owned token must exist");
+                owned_tokens.insert((*from, removed_owner_index), last_owner_token);
+                owned_tokens_index.insert(last_owner_token, removed_owner_index);
+            }
+            owned_tokens.take(&(*from, last_owner_index));
+
+            self.record_checkpoint(from);
             Ok(())
         }
 
@@ -310,6 +682,21 @@ mod erc721 {
         entry.and_modify(|v| *v += 1).or_insert(1);
     }
 
+    fn increase_balance_of(entry: Entry<(TokenId, AccountId), u128>, amount: u128) {
+        entry.and_modify(|v| *v += amount).or_insert(amount);
+    }
+
+    fn increase_total_of(entry: Entry<TokenId, u128>, amount: u128) {
+        entry.and_modify(|v| *v += amount).or_insert(amount);
+    }
+
+    // Turns a recovered public key into an AccountId.
+    fn to_account_id(compressed_pubkey: &[u8; 33]) -> AccountId {
+        let mut account_id = [0u8; 32];
+        ink_env::hash_bytes::<ink_env::hash::Blake2x256>(compressed_pubkey, &mut account_id);
+        account_id.into()
+    }
+
 
 
     #[cfg(test)]
@@ -324,7 +711,7 @@ mod erc721 {
         #[ink::test]
         fn mint_works() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut erc721 = Erc721::new();
+            let mut erc721 = Erc721::new(accounts.django);
             // Token 1 does not exists.
             assert_eq!(erc721.owner_of(1), None);
             // Alice does not owns tokens.
@@ -338,7 +725,7 @@ mod erc721 {
         #[ink::test]
         fn mint_existing_should_fail() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut erc721 = Erc721::new();
+            let mut erc721 = Erc721::new(accounts.django);
             // Create token Id 1.
             assert_eq!(erc721.mint(1), Ok(()));
             // The first Transfer event tasks place.
@@ -353,7 +740,7 @@ mod erc721 {
         #[ink::test]
         fn transfer_works() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut erc721 = Erc721::new();
+            let mut erc721 = Erc721::new(accounts.django);
             // Create token Id 1 for Alice.
             assert_eq!(erc721.mint(1), Ok(()));
             // Alice owns token 1.
@@ -375,7 +762,7 @@ mod erc721 {
         #[ink::test]
         fn invalid_transfer_should_fail() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut erc721 = Erc721::new();
+            let mut erc721 = Erc721::new(accounts.django);
             // Transfer tokn fails if it does not exits.
             assert_eq!(erc721.transfer(accounts.bob, 2), Err(Error::TokenNotFound));
             // Token Id 2 does not exits.
@@ -407,7 +794,7 @@ mod erc721 {
         #[ink::test]
         fn approved_transfer_works() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut erc721 = Erc721::new();
+            let mut erc721 = Erc721::new(accounts.django);
             assert_eq!(erc721.mint(1), Ok(()));
             assert_eq!(erc721.owner_of(1), Some(accounts.alice));
             assert_eq!(erc721.approve(accounts.bob, 1), Ok(()));
@@ -432,7 +819,7 @@ mod erc721 {
         #[ink::test]
         fn approved_for_all_works() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut erc721 = Erc721::new();
+            let mut erc721 = Erc721::new(accounts.django);
             assert_eq!(erc721.mint(1), Ok(()));
             assert_eq!(erc721.mint(2), Ok(()));
             assert_eq!(erc721.balance_of(accounts.alice), 2);
@@ -465,7 +852,7 @@ mod erc721 {
         #[ink::test]
         fn not_approved_transfer_should_fail() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut erc721 = Erc721::new();
+            let mut erc721 = Erc721::new(accounts.django);
             assert_eq!(erc721.mint(1), Ok(()));
             assert_eq!(erc721.balance_of(accounts.alice), 1);
             assert_eq!(erc721.balance_of(accounts.bob), 0);
@@ -490,7 +877,7 @@ mod erc721 {
         #[ink::test]
         fn burn_works() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut erc721 = Erc721::new();
+            let mut erc721 = Erc721::new(accounts.django);
             assert_eq!(erc721.mint(1), Ok(()));
             assert_eq!(erc721.balance_of(accounts.alice), 1);
             assert_eq!(erc721.owner_of(1), Some(accounts.alice));
@@ -501,20 +888,318 @@ mod erc721 {
 
         #[ink::test]
         fn burn_fails_token_not_found() {
-            let mut erc721 = Erc721::new();
+            let mut erc721 = Erc721::new(AccountId::from([0x1; 32]));
             assert_eq!(erc721.burn(1), Err(Error::TokenNotFound));
         }
 
         #[ink::test]
         fn burn_failed_not_owner() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut erc721 = Erc721::new();
+            let mut erc721 = Erc721::new(accounts.django);
             assert_eq!(erc721.mint(1), Ok(()));
             // Try burning this token with a different account.
             set_sender(accounts.eve);
             assert_eq!(erc721.burn(1), Err(Error::NotOwner));
         }
 
+        #[ink::test]
+        fn total_supply_and_enumeration_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            assert_eq!(erc721.total_supply(), 0);
+            assert_eq!(erc721.mint(1), Ok(()));
+            assert_eq!(erc721.mint(2), Ok(()));
+            assert_eq!(erc721.total_supply(), 2);
+            assert_eq!(erc721.token_by_index(0), Some(1));
+            assert_eq!(erc721.token_by_index(1), Some(2));
+            assert_eq!(erc721.token_by_index(2), None);
+            assert_eq!(erc721.token_of_owner_by_index(accounts.alice, 0), Some(1));
+            assert_eq!(erc721.token_of_owner_by_index(accounts.alice, 1), Some(2));
+
+            // Burning token 1 swaps token 2 into its slot.
+            assert_eq!(erc721.burn(1), Ok(()));
+            assert_eq!(erc721.total_supply(), 1);
+            assert_eq!(erc721.token_by_index(0), Some(2));
+            assert_eq!(erc721.token_by_index(1), None);
+            assert_eq!(erc721.token_of_owner_by_index(accounts.alice, 0), Some(2));
+            assert_eq!(erc721.token_of_owner_by_index(accounts.alice, 1), None);
+        }
+
+        #[ink::test]
+        fn safe_transfer_to_account_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            assert_eq!(erc721.mint(1), Ok(()));
+            // Bob is a plain account, so no `on_erc721_received` callback is required.
+            assert_eq!(erc721.safe_transfer_from(accounts.alice, accounts.bob, 1, Vec::new()), Ok(()));
+            assert_eq!(erc721.owner_of(1), Some(accounts.bob));
+        }
+
+        #[ink::test]
+        fn mint_with_receipt_rejects_bad_signature() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            // Signature is not from bridge_authority.
+            assert_eq!(
+                erc721.mint_with_receipt(1, accounts.alice, 0, [0u8; 65]),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(erc721.owner_of(1), None);
+        }
+
+        #[ink::test]
+        fn mint_with_receipt_rejects_reused_nonce() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            assert_eq!(
+                erc721.mint_with_receipt(1, accounts.alice, 0, [0u8; 65]),
+                Err(Error::InvalidSignature)
+            );
+            // A used nonce is rejected even before the signature is checked.
+            erc721.consumed_nonces.insert(0, true);
+            assert_eq!(
+                erc721.mint_with_receipt(1, accounts.alice, 0, [0u8; 65]),
+                Err(Error::ReceiptAlreadyUsed)
+            );
+        }
+
+        #[ink::test]
+        fn mint_with_receipt_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            // bridge_authority derived from a real secp256k1 key, and a signature over
+            // the domain tag plus (accounts.alice, id 7, nonce 0) signed by that key.
+            let bridge_authority = AccountId::from([
+                0x86, 0xcc, 0xc8, 0x61, 0xe0, 0xcd, 0x53, 0xc8, 0x88, 0x7c, 0x0d, 0x9a, 0x4e, 0x4f,
+                0x1c, 0x1e, 0x0e, 0x36, 0xb5, 0xae, 0x5f, 0xfc, 0xcb, 0xff, 0xa3, 0x2e, 0xb9, 0xb9,
+                0x14, 0xb9, 0x93, 0x93,
+            ]);
+            let signature: [u8; 65] = [
+                0x40, 0x76, 0x9b, 0x92, 0xab, 0x86, 0xe4, 0x5f, 0xe4, 0x52, 0x7d, 0x7b, 0x03, 0x18,
+                0x6b, 0x64, 0x22, 0x5e, 0xd7, 0xd6, 0xbf, 0x40, 0xe4, 0x2e, 0x43, 0xd7, 0x5a, 0x2f,
+                0x5c, 0x37, 0xc8, 0x8b, 0x65, 0xdd, 0xa0, 0x0f, 0x47, 0xf7, 0x15, 0x9d, 0x6a, 0x99,
+                0x0b, 0x7d, 0x42, 0x3b, 0xcb, 0x1f, 0x05, 0x29, 0xf3, 0x4d, 0x72, 0x2d, 0x7e, 0xfd,
+                0x8f, 0xc0, 0x48, 0xe7, 0xbb, 0xdb, 0x49, 0xbf, 0x00,
+            ];
+            let mut erc721 = Erc721::new(bridge_authority);
+            assert_eq!(
+                erc721.mint_with_receipt(7, accounts.alice, 0, signature),
+                Ok(())
+            );
+            assert_eq!(erc721.owner_of(7), Some(accounts.alice));
+            // Replaying the same nonce is rejected even with the same valid signature.
+            assert_eq!(
+                erc721.mint_with_receipt(7, accounts.alice, 0, signature),
+                Err(Error::ReceiptAlreadyUsed)
+            );
+        }
+
+        #[ink::test]
+        fn permit_rejects_bad_signature() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            assert_eq!(erc721.mint(1), Ok(()));
+            assert_eq!(
+                erc721.permit(accounts.alice, accounts.bob, 1, u64::MAX, [0u8; 65]),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(erc721.get_approved(1), None);
+        }
+
+        #[ink::test]
+        fn permit_rejects_expired_deadline() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            assert_eq!(erc721.mint(1), Ok(()));
+            // Advance the block past deadline 0.
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            assert_eq!(
+                erc721.permit(accounts.alice, accounts.bob, 1, 0, [0u8; 65]),
+                Err(Error::PermitExpired)
+            );
+        }
+
+        #[ink::test]
+        fn permit_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            // Owner account derived from a real secp256k1 key, and a signature over
+            // (owner, accounts.bob, 1, nonce 0, deadline u64::MAX) signed by that key.
+            let owner = AccountId::from([
+                0x76, 0x7e, 0x45, 0x81, 0x13, 0xdd, 0xd1, 0x81, 0x1f, 0x37, 0x61, 0x7a, 0xd9, 0x50,
+                0x19, 0x3d, 0x63, 0xc8, 0x82, 0x29, 0x6f, 0xe9, 0x4c, 0xb7, 0xf3, 0xa6, 0xb4, 0x43,
+                0x66, 0xbb, 0xa3, 0xad,
+            ]);
+            let signature: [u8; 65] = [
+                0x13, 0xb9, 0x8a, 0xad, 0x62, 0xbd, 0x52, 0x74, 0x35, 0xd8, 0x04, 0xd8, 0x71, 0x9b,
+                0x75, 0xbc, 0x42, 0x5c, 0xf7, 0x09, 0xc8, 0x72, 0x4f, 0xe0, 0xd9, 0xf6, 0x81, 0xe8,
+                0x21, 0x48, 0xbc, 0xc3, 0x27, 0x46, 0xbd, 0x3e, 0x57, 0x72, 0x1f, 0x93, 0xd0, 0x2b,
+                0x0f, 0x18, 0x3b, 0xde, 0xc9, 0x55, 0x38, 0xb0, 0xad, 0xa1, 0xc9, 0x80, 0x3b, 0x22,
+                0x40, 0x40, 0x80, 0xbe, 0x46, 0x96, 0xe1, 0x9a, 0x01,
+            ];
+            let mut erc721 = Erc721::new(accounts.django);
+            // Mint token 1 to owner so permit's ownership check passes.
+            set_sender(owner);
+            assert_eq!(erc721.mint(1), Ok(()));
+            assert_eq!(
+                erc721.permit(owner, accounts.bob, 1, u64::MAX, signature),
+                Ok(())
+            );
+            assert_eq!(erc721.get_approved(1), Some(accounts.bob));
+            // Nonce bumped so the same signature cannot be replayed.
+            assert_eq!(erc721.permit_nonces.get(&owner), Some(&1));
+        }
+
+        #[ink::test]
+        fn permit_rejects_valid_signature_from_non_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            // Same owner/signature pair as permit_works, but token 1 is owned by Alice
+            // instead, so the signature recovers correctly yet isn't the token's owner.
+            let owner = AccountId::from([
+                0x76, 0x7e, 0x45, 0x81, 0x13, 0xdd, 0xd1, 0x81, 0x1f, 0x37, 0x61, 0x7a, 0xd9, 0x50,
+                0x19, 0x3d, 0x63, 0xc8, 0x82, 0x29, 0x6f, 0xe9, 0x4c, 0xb7, 0xf3, 0xa6, 0xb4, 0x43,
+                0x66, 0xbb, 0xa3, 0xad,
+            ]);
+            let signature: [u8; 65] = [
+                0x13, 0xb9, 0x8a, 0xad, 0x62, 0xbd, 0x52, 0x74, 0x35, 0xd8, 0x04, 0xd8, 0x71, 0x9b,
+                0x75, 0xbc, 0x42, 0x5c, 0xf7, 0x09, 0xc8, 0x72, 0x4f, 0xe0, 0xd9, 0xf6, 0x81, 0xe8,
+                0x21, 0x48, 0xbc, 0xc3, 0x27, 0x46, 0xbd, 0x3e, 0x57, 0x72, 0x1f, 0x93, 0xd0, 0x2b,
+                0x0f, 0x18, 0x3b, 0xde, 0xc9, 0x55, 0x38, 0xb0, 0xad, 0xa1, 0xc9, 0x80, 0x3b, 0x22,
+                0x40, 0x40, 0x80, 0xbe, 0x46, 0x96, 0xe1, 0x9a, 0x01,
+            ];
+            let mut erc721 = Erc721::new(accounts.django);
+            // Token 1 belongs to Alice, not to owner.
+            assert_eq!(erc721.mint(1), Ok(()));
+            assert_eq!(
+                erc721.permit(owner, accounts.bob, 1, u64::MAX, signature),
+                Err(Error::NotAllowed)
+            );
+            assert_eq!(erc721.get_approved(1), None);
+        }
+
+        #[ink::test]
+        fn mint_amount_and_batch_transfer_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            assert_eq!(erc721.mint_amount(1, 10), Ok(()));
+            assert_eq!(erc721.mint_amount(2, 5), Ok(()));
+            assert_eq!(
+                erc721.balance_of_batch(vec![accounts.alice, accounts.alice], vec![1, 2]),
+                Ok(vec![10, 5])
+            );
+
+            assert_eq!(erc721.batch_transfer(accounts.bob, vec![1, 2], vec![4, 5]), Ok(()));
+            assert_eq!(
+                erc721.balance_of_batch(vec![accounts.alice, accounts.alice], vec![1, 2]),
+                Ok(vec![6, 0])
+            );
+            assert_eq!(
+                erc721.balance_of_batch(vec![accounts.bob, accounts.bob], vec![1, 2]),
+                Ok(vec![4, 5])
+            );
+        }
+
+        #[ink::test]
+        fn balance_of_batch_rejects_mismatched_lengths() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            assert_eq!(erc721.mint_amount(1, 10), Ok(()));
+            // One more owner than id: must error instead of silently zipping to 1 entry.
+            assert_eq!(
+                erc721.balance_of_batch(vec![accounts.alice, accounts.bob], vec![1]),
+                Err(Error::NotAllowed)
+            );
+        }
+
+        #[ink::test]
+        fn batch_transfer_reverts_whole_batch_on_insufficient_balance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            assert_eq!(erc721.mint_amount(1, 10), Ok(()));
+            assert_eq!(erc721.mint_amount(2, 1), Ok(()));
+            // Token 2 is insufficient so token 1 must not move either.
+            assert_eq!(
+                erc721.batch_transfer(accounts.bob, vec![1, 2], vec![5, 5]),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(
+                erc721.balance_of_batch(vec![accounts.alice, accounts.bob], vec![1, 1]),
+                Ok(vec![10, 0])
+            );
+        }
+
+        #[ink::test]
+        fn batch_transfer_rejects_duplicate_id_exceeding_aggregate_balance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            assert_eq!(erc721.mint_amount(1, 10), Ok(()));
+            // Each leg alone is fine, but the combined total for id 1 is not.
+            assert_eq!(
+                erc721.batch_transfer(accounts.bob, vec![1, 1], vec![6, 6]),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(
+                erc721.balance_of_batch(vec![accounts.alice, accounts.bob], vec![1, 1]),
+                Ok(vec![10, 0])
+            );
+        }
+
+        #[ink::test]
+        fn mint_amount_fails_if_id_is_single_owner_token() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            assert_eq!(erc721.mint(1), Ok(()));
+            assert_eq!(erc721.mint_amount(1, 10), Err(Error::TokenExists));
+        }
+
+        #[ink::test]
+        fn mint_fails_if_id_is_quantity_token() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            assert_eq!(erc721.mint_amount(1, 10), Ok(()));
+            assert_eq!(erc721.mint(1), Err(Error::TokenExists));
+        }
+
+        #[ink::test]
+        fn balance_of_at_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+
+            // No checkpoint yet, balance is 0.
+            assert_eq!(erc721.balance_of_at(accounts.alice, 0), 0);
+
+            // Mint after genesis so the block before the mint can be queried.
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            let block_at_mint = ink_env::block_number::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc721.mint(1), Ok(()));
+            assert_eq!(erc721.balance_of_at(accounts.alice, block_at_mint), 1);
+            assert_eq!(erc721.balance_of_at(accounts.alice, block_at_mint.saturating_sub(1)), 0);
+
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            let block_at_transfer = ink_env::block_number::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc721.transfer(accounts.bob, 1), Ok(()));
+
+            // Mint block balance unaffected by the later transfer.
+            assert_eq!(erc721.balance_of_at(accounts.alice, block_at_mint), 1);
+            assert_eq!(erc721.balance_of_at(accounts.alice, block_at_transfer), 0);
+            assert_eq!(erc721.balance_of_at(accounts.bob, block_at_transfer), 1);
+            // Query after the last checkpoint just returns current balance.
+            assert_eq!(erc721.balance_of_at(accounts.bob, block_at_transfer + 100), 1);
+        }
+
+        #[ink::test]
+        fn balance_of_at_reflects_burn() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut erc721 = Erc721::new(accounts.django);
+            assert_eq!(erc721.mint(1), Ok(()));
+
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            let block_at_burn = ink_env::block_number::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc721.burn(1), Ok(()));
+
+            // Burn must record the drop to 0 too, not just the mint.
+            assert_eq!(erc721.balance_of_at(accounts.alice, block_at_burn), 0);
+            assert_eq!(erc721.balance_of_at(accounts.alice, block_at_burn + 100), 0);
+        }
+
         fn set_sender(sender: AccountId) {
             let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
             test::push_execution_context::<Environment>(